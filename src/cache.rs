@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::content::ContentError;
+
+// Default max age before a cached docs.rs page is considered stale
+// and re-downloaded, in seconds.
+pub const DEFAULT_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("crate-docs")
+}
+
+fn entry_paths(crate_name: &str, version: &str) -> (PathBuf, PathBuf) {
+    let dir = cache_dir();
+    let key = format!("{}@{}", crate_name, version);
+    (dir.join(format!("{}.html", key)), dir.join(format!("{}.meta", key)))
+}
+
+// Returns the cached page body if it exists and is younger than
+// `max_age` seconds.
+pub fn read_cached(crate_name: &str, version: &str, max_age: u64) -> Option<String> {
+    let (body_path, meta_path) = entry_paths(crate_name, version);
+    let meta = fs::read_to_string(&meta_path).ok()?;
+    let fetched_at: u64 = meta.trim().parse().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    if now.saturating_sub(fetched_at) > max_age {
+        return None;
+    }
+    fs::read_to_string(&body_path).ok()
+}
+
+// Finds the freshest cached entry for `crate_name` regardless of
+// version, for callers (the offline provider) that don't know which
+// version was last resolved. Returns the matched version alongside
+// its body so the caller can rebuild the page's base url.
+pub fn read_latest_cached(crate_name: &str, max_age: u64) -> Option<(String, String)> {
+    let dir = cache_dir();
+    let prefix = format!("{}@", crate_name);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    let mut best: Option<(u64, String)> = None;
+    for entry in fs::read_dir(&dir).ok()?.flatten() {
+        let file_name = match entry.file_name().into_string() {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        let version = match file_name.strip_prefix(&prefix).and_then(|v| v.strip_suffix(".meta")) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let fetched_at: u64 = match fs::read_to_string(entry.path()).ok().and_then(|s| s.trim().parse().ok()) {
+            Some(t) => t,
+            None => continue,
+        };
+        if now.saturating_sub(fetched_at) > max_age {
+            continue;
+        }
+
+        if best.as_ref().map_or(true, |(t, _)| fetched_at > *t) {
+            best = Some((fetched_at, version.to_owned()));
+        }
+    }
+
+    let (_, version) = best?;
+    let (body_path, _) = entry_paths(crate_name, &version);
+    let body = fs::read_to_string(body_path).ok()?;
+    Some((version, body))
+}
+
+pub fn write_cache(crate_name: &str, version: &str, body: &str) -> Result<(), ContentError> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir).map_err(|_| ContentError::LoadFailure)?;
+    let (body_path, meta_path) = entry_paths(crate_name, version);
+    fs::write(&body_path, body).map_err(|_| ContentError::LoadFailure)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    fs::write(&meta_path, now.to_string()).map_err(|_| ContentError::LoadFailure)?;
+    Ok(())
+}
+
+pub fn max_age_from_env() -> u64 {
+    std::env::var("CRATE_DOCS_CACHE_MAX_AGE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_AGE_SECS)
+}
+
+// Reads an optional HTTP(S) proxy for the reqwest client, preferring
+// our own env var over the conventional `HTTPS_PROXY`.
+pub fn proxy_from_env() -> Option<String> {
+    std::env::var("CRATE_DOCS_HTTP_PROXY")
+        .or_else(|_| std::env::var("HTTPS_PROXY"))
+        .or_else(|_| std::env::var("https_proxy"))
+        .ok()
+}
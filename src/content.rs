@@ -1,13 +1,17 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::env;
 
 use url::Url;
 use reqwest;
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::Deserialize;
 use prettytable::{Table, Row, Cell};
 use prettytable::format;
 
+use crate::cache;
+
 #[derive(Debug)]
 pub enum ContentError {
     DoesNotExist,
@@ -19,6 +23,7 @@ pub enum ContentError {
 pub enum PageType {
     All(Html),
     Index(Html),
+    Json,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -33,6 +38,21 @@ pub enum DocType {
     Other,
 }
 
+impl DocType {
+    fn from_rustdoc_kind(kind: &str) -> Self {
+        match kind {
+            "module" => DocType::Module,
+            "struct" => DocType::Struct,
+            "typedef" => DocType::Type,
+            "trait" => DocType::Trait,
+            "enum" => DocType::Enum,
+            "function" => DocType::Function,
+            "constant" => DocType::Constant,
+            _ => DocType::Other,
+        }
+    }
+}
+
 impl From<&DocType> for String {
     fn from(dt: &DocType) -> Self {
         match dt {
@@ -86,20 +106,13 @@ pub struct DocPage {
 
 impl DocPage {
     pub fn fetch(online: bool, crate_name: &str) -> Result<Self, ContentError> {
-        let page = fetch_html(crate_name, online);
-        match page {
-            Err(e) => Err(e),
-            Ok((p, u)) => {
-                let docs = gen_doc_listings(&p, &u);
-                match docs {
-                    Err(e) => Err(e),
-                    Ok(d) => Ok(Self {
-                        page_type: p,
-                        doc_blocks: d,
-                    })
-                }
-            }
-        }
+        ProviderRegistry::default().fetch(online, crate_name)
+    }
+
+    // Forces a re-download of the currently cached docs.rs page for
+    // `crate_name`, bypassing the TTL check.
+    pub fn refresh_cache(crate_name: &str) -> Result<(), ContentError> {
+        fetch_live_html_inner(crate_name, true).map(|_| ())
     }
 
     pub fn print_tableview(&self) {
@@ -121,6 +134,91 @@ impl DocPage {
     }
 }
 
+/// A source of crate documentation. Separates URL construction and
+/// parsing for a given backend from how the REPL picks one.
+pub trait DocProvider {
+    fn fetch(&self, crate_name: &str) -> Result<DocPage, ContentError>;
+}
+
+/// Reads docs.rs over the network, backed by the on-disk page cache.
+pub struct DocsRsProvider;
+
+impl DocProvider for DocsRsProvider {
+    fn fetch(&self, crate_name: &str) -> Result<DocPage, ContentError> {
+        let (p, u) = fetch_live_html(crate_name)?;
+        let docs = gen_doc_listings(&p, &u)?;
+        Ok(DocPage {
+            page_type: p,
+            doc_blocks: docs,
+        })
+    }
+}
+
+/// Reads a crate's docs out of the local `target/doc/` tree, preferring
+/// rustdoc's JSON output when present and falling back to `all.html`.
+pub struct LocalRustdocProvider;
+
+impl DocProvider for LocalRustdocProvider {
+    fn fetch(&self, crate_name: &str) -> Result<DocPage, ContentError> {
+        if let Some(json_path) = rustdoc_json_path(crate_name) {
+            let docs = gen_doc_listings_from_json(&json_path, crate_name)?;
+            return Ok(DocPage {
+                page_type: PageType::Json,
+                doc_blocks: docs,
+            });
+        }
+
+        let (p, u) = fetch_html(crate_name, false)?;
+        let docs = gen_doc_listings(&p, &u)?;
+        Ok(DocPage {
+            page_type: p,
+            doc_blocks: docs,
+        })
+    }
+}
+
+/// Routes a `lup` query to a doc provider. Queries prefixed with
+/// `<name>::` are routed to whichever provider registered that
+/// prefix - e.g. `lup docsrs::tokio` or `lup local::tokio` force a
+/// specific backend - so a future stdlib or private-host provider
+/// can be added under its own prefix without touching the REPL. A
+/// crate name never legitimately contains `::`, so any prefix that
+/// isn't registered is treated as a typo'd routing request rather
+/// than fed to a provider as a literal crate name. Anything
+/// unprefixed falls back to the `"local"`/`"docsrs"` entries based on
+/// `online`.
+pub struct ProviderRegistry {
+    named: Vec<(String, Box<dyn DocProvider>)>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        let mut registry = Self { named: Vec::new() };
+        registry.register("local", Box::new(LocalRustdocProvider));
+        registry.register("docsrs", Box::new(DocsRsProvider));
+        registry
+    }
+}
+
+impl ProviderRegistry {
+    pub fn register(&mut self, prefix: &str, provider: Box<dyn DocProvider>) {
+        self.named.push((prefix.to_owned(), provider));
+    }
+
+    fn provider(&self, prefix: &str) -> Option<&dyn DocProvider> {
+        self.named.iter().find(|(p, _)| p == prefix).map(|(_, provider)| provider.as_ref())
+    }
+
+    pub fn fetch(&self, online: bool, crate_name: &str) -> Result<DocPage, ContentError> {
+        if let Some((prefix, rest)) = crate_name.split_once("::") {
+            return self.provider(prefix).ok_or(ContentError::DoesNotExist)?.fetch(rest);
+        }
+
+        let fallback = if online { "docsrs" } else { "local" };
+        self.provider(fallback).ok_or(ContentError::DoesNotExist)?.fetch(crate_name)
+    }
+}
+
 pub fn gen_doc_listings(page: &PageType, base_url: &str) -> Result<Vec<DocTypeListing>, ContentError> {
     match page {
         PageType::All(html) => {
@@ -212,18 +310,180 @@ pub fn gen_doc_listings(page: &PageType, base_url: &str) -> Result<Vec<DocTypeLi
     }
 }
 
+// Mirrors the bits of rustdoc's `--output-format json` document we
+// actually need: an index of items keyed by id, and a paths table
+// that gives each item's module path and kind so we can rebuild the
+// same doc URL the HTML output would have used.
+#[derive(Debug, Deserialize)]
+struct RustdocJson {
+    index: HashMap<String, RustdocItem>,
+    paths: HashMap<String, RustdocItemSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustdocItem {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustdocItemSummary {
+    kind: String,
+    path: Vec<String>,
+}
+
+fn rustdoc_json_path(crate_name: &str) -> Option<String> {
+    let path = env::current_dir().ok()?;
+    let json_path = format!("{}/target/doc/{}.json", path.display(), crate_name);
+    if std::path::Path::new(&json_path).exists() {
+        Some(json_path)
+    } else {
+        None
+    }
+}
+
+// Builds the relative path rustdoc's HTML output would have used for
+// an item, given its module path with the crate name already
+// stripped off the front, e.g. `["bar", "Baz"], "struct", "Baz"` ->
+// `bar/struct.Baz.html`. Modules are their own directory (`path`
+// includes the module's own name); everything else lives as a file
+// inside the directory named by all but the last `path` segment.
+fn rustdoc_item_url(path_after_crate: &[String], kind: &DocType, name: &str) -> String {
+    if let DocType::Module = kind {
+        return format!("{}/index.html", path_after_crate.join("/"));
+    }
+
+    let dir_len = path_after_crate.len().saturating_sub(1);
+    let dir = path_after_crate[..dir_len].join("/");
+    let filename = match kind {
+        DocType::Struct => format!("struct.{}.html", name),
+        DocType::Trait => format!("trait.{}.html", name),
+        DocType::Enum => format!("enum.{}.html", name),
+        DocType::Function => format!("fn.{}.html", name),
+        DocType::Constant => format!("constant.{}.html", name),
+        DocType::Type => format!("type.{}.html", name),
+        DocType::Module | DocType::Other => "index.html".to_owned(),
+    };
+
+    if dir.is_empty() {
+        filename
+    } else {
+        format!("{}/{}", dir, filename)
+    }
+}
+
+pub fn gen_doc_listings_from_json(json_path: &str, crate_name: &str) -> Result<Vec<DocTypeListing>, ContentError> {
+    let mut content = String::new();
+    match File::open(json_path) {
+        Err(_) => return Err(ContentError::DoesNotExist),
+        Ok(mut f) => {
+            if f.read_to_string(&mut content).is_err() {
+                return Err(ContentError::LoadFailure);
+            }
+        }
+    }
+
+    let doc: RustdocJson = match serde_json::from_str(&content) {
+        Ok(d) => d,
+        Err(_) => return Err(ContentError::InvalidPage),
+    };
+
+    // The doc root for a crate's generated HTML lives alongside the
+    // JSON file, under `target/doc/<crate_name>/`.
+    let base_dir = json_path.replace(&format!("{}.json", crate_name), crate_name);
+
+    let mut grouped: HashMap<String, Vec<DocListing>> = HashMap::new();
+    let order = ["Modules", "Structs", "Types", "Traits", "Enums", "Functions", "Constants"];
+
+    for (id, item) in &doc.index {
+        let name = match &item.name {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let summary = match doc.paths.get(id) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        // `summary.path` is crate-name-prefixed (e.g. `["foo", "bar", "Baz"]`);
+        // `base_dir` already ends in the crate name, so strip it here
+        // rather than duplicating it in the joined url.
+        let path_after_crate = if summary.path.is_empty() { &summary.path[..] } else { &summary.path[1..] };
+
+        let dtype = DocType::from_rustdoc_kind(&summary.kind);
+        let url = format!("{}/{}", base_dir, rustdoc_item_url(path_after_crate, &dtype, name));
+
+        grouped.entry(String::from(&dtype)).or_insert_with(Vec::new).push(DocListing {
+            name: name.to_owned(),
+            url,
+        });
+    }
+
+    let mut docs: Vec<DocTypeListing> = Vec::new();
+    for type_name in order {
+        if let Some(listings) = grouped.remove(type_name) {
+            let doc_type = match type_name {
+                "Modules" => DocType::Module,
+                "Structs" => DocType::Struct,
+                "Types" => DocType::Type,
+                "Traits" => DocType::Trait,
+                "Enums" => DocType::Enum,
+                "Functions" => DocType::Function,
+                "Constants" => DocType::Constant,
+                _ => DocType::Other,
+            };
+            docs.push(DocTypeListing { doc_type, docs: listings });
+        }
+    }
+
+    Ok(docs)
+}
+
 pub fn fetch_live_html(crate_name: &str) -> Result<(PageType, String), ContentError> {
+    fetch_live_html_inner(crate_name, false)
+}
+
+// Builds a blocking reqwest client, honoring an optional proxy
+// configured via `cache::proxy_from_env`. Used by every reqwest call
+// in this crate so the proxy setting applies uniformly.
+pub(crate) fn build_http_client() -> Result<reqwest::blocking::Client, ContentError> {
+    let mut builder = reqwest::blocking::Client::builder();
+    if let Some(proxy_url) = cache::proxy_from_env() {
+        let proxy = reqwest::Proxy::all(&proxy_url).map_err(|_| ContentError::LoadFailure)?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|_| ContentError::LoadFailure)
+}
+
+// docs.rs redirects `docs.rs/<crate>` to `docs.rs/<crate>/<version>/<crate>/`,
+// so the resolved version is the second-to-last path segment.
+fn extract_version(resolved_url: &str) -> Option<String> {
+    let trimmed = resolved_url.trim_end_matches('/');
+    let segments: Vec<&str> = trimmed.rsplit('/').collect();
+    segments.get(1).map(|s| s.to_string())
+}
+
+fn fetch_live_html_inner(crate_name: &str, force: bool) -> Result<(PageType, String), ContentError> {
+    let client = build_http_client()?;
     let url = format!("https://docs.rs/{}", crate_name);
-    let resp = reqwest::blocking::get(&url);
+    let resp = client.get(&url).send();
     match resp {
         Ok(r) => {
             // We cannot know the exact url up front
             // since the url includes the version. However,
             // we can hit the base url and check what it resolves
-            // to and then use that resolved url to get the 
+            // to and then use that resolved url to get the
             // all.html page.
             let url = format!("{}all.html", r.url());
-            let resp = reqwest::blocking::get(&url);
+            let version = extract_version(r.url().as_str()).unwrap_or_else(|| "latest".to_owned());
+
+            if !force {
+                if let Some(cached) = cache::read_cached(crate_name, &version, cache::max_age_from_env()) {
+                    return Ok((PageType::All(Html::parse_document(&cached)), url));
+                }
+            }
+
+            let resp = client.get(&url).send();
             match resp {
                 Err(_) => Err(ContentError::LoadFailure),
                 Ok(r) => {
@@ -232,7 +492,10 @@ pub fn fetch_live_html(crate_name: &str) -> Result<(PageType, String), ContentEr
                             let body = r.text();
                             match body {
                                 Err(_) => Err(ContentError::LoadFailure),
-                                Ok(b) => Ok((PageType::All(Html::parse_document(&b)), url))
+                                Ok(b) => {
+                                    let _ = cache::write_cache(crate_name, &version, &b);
+                                    Ok((PageType::All(Html::parse_document(&b)), url))
+                                }
                             }
                         },
                         _ =>Err(ContentError::DoesNotExist),
@@ -261,7 +524,18 @@ pub fn fetch_html(crate_name: &str, online: bool) -> Result<(PageType, String),
     let index_path = format!("{}/target/doc/{}/all.html", path, crate_name);
     let file = File::open(index_path.clone());
     match file {
-        Err(_) => Err(ContentError::DoesNotExist),
+        Err(_) => {
+            // No local `cargo doc` output - fall back to whatever
+            // docs.rs page we last cached for this crate, if any,
+            // so offline lookups can still succeed.
+            match cache::read_latest_cached(crate_name, cache::max_age_from_env()) {
+                Some((version, body)) => {
+                    let url = format!("https://docs.rs/{}/{}/{}/all.html", crate_name, version, crate_name);
+                    Ok((PageType::All(Html::parse_document(&body)), url))
+                }
+                None => Err(ContentError::DoesNotExist),
+            }
+        },
         Ok(mut f) => {
             let mut content = String::new();
             match f.read_to_string(&mut content) {
@@ -270,4 +544,86 @@ pub fn fetch_html(crate_name: &str, online: bool) -> Result<(PageType, String),
             }
         }
     }
+}
+
+// Loads a single rustdoc page, either over http(s) or from disk,
+// the same way `fetch_html` loads `all.html`.
+fn load_doc_html(location: &str, online: bool) -> Result<Html, ContentError> {
+    if online {
+        let client = build_http_client()?;
+        match client.get(location).send() {
+            Ok(r) => match r.text() {
+                Ok(b) => Ok(Html::parse_document(&b)),
+                Err(_) => Err(ContentError::LoadFailure),
+            },
+            Err(_) => Err(ContentError::LoadFailure),
+        }
+    } else {
+        match File::open(location) {
+            Ok(mut f) => {
+                let mut content = String::new();
+                match f.read_to_string(&mut content) {
+                    Ok(_) => Ok(Html::parse_document(&content)),
+                    Err(_) => Err(ContentError::LoadFailure),
+                }
+            },
+            Err(_) => Err(ContentError::DoesNotExist),
+        }
+    }
+}
+
+// Walks an element's children, converting the tags rustdoc uses for
+// item docs into roughly equivalent Markdown.
+fn render_node_markdown(node: ElementRef) -> String {
+    let mut out = String::new();
+    for child in node.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(text),
+            Node::Element(el) => {
+                let child_ref = match ElementRef::wrap(child) {
+                    Some(r) => r,
+                    None => continue,
+                };
+                let inner = render_node_markdown(child_ref);
+                match el.name() {
+                    "p" => out.push_str(&format!("{}\n\n", inner.trim())),
+                    "code" => out.push_str(&format!("`{}`", inner.trim())),
+                    "pre" => out.push_str(&format!("```\n{}\n```\n\n", inner.trim())),
+                    "li" => out.push_str(&format!("- {}\n", inner.trim())),
+                    "ul" | "ol" => out.push_str(&format!("{}\n", inner)),
+                    "a" => {
+                        let href = el.attr("href").unwrap_or("");
+                        out.push_str(&format!("[{}]({})", inner.trim(), href));
+                    },
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        let level: usize = el.name()[1..].parse().unwrap_or(1);
+                        out.push_str(&format!("{} {}\n\n", "#".repeat(level), inner.trim()));
+                    },
+                    _ => out.push_str(&inner),
+                }
+            },
+            _ => {}
+        }
+    }
+    out
+}
+
+// Fetches the page a `DocListing` points at and extracts every
+// `.docblock` section on it, each rendered as terminal-friendly
+// Markdown and joined with blank lines.
+pub fn fetch_doc_body(listing: &DocListing, online: bool) -> Result<String, ContentError> {
+    let html = load_doc_html(&listing.url, online)?;
+    let selector = Selector::parse(".docblock").unwrap();
+
+    let mut body = String::new();
+    for block in html.select(&selector) {
+        body.push_str(render_node_markdown(block).trim());
+        body.push_str("\n\n");
+    }
+
+    if body.trim().is_empty() {
+        return Err(ContentError::InvalidPage);
+    }
+
+    Ok(body.trim().to_owned())
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+// A Smith-Waterman-style subsequence scorer: `query`'s characters
+// must appear in `candidate` in order (not necessarily contiguous),
+// with bonuses for matches that land on a word/`::` boundary or a
+// camelCase hump, and a small penalty for each candidate character
+// skipped between matches.
+// Returns `None` if `query` is empty or isn't a subsequence of
+// `candidate` at all.
+pub fn score(candidate: &str, query: &str) -> Option<i32> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let cand: Vec<char> = candidate.chars().collect();
+    let query: Vec<char> = query.chars().collect();
+    let n = cand.len();
+    let m = query.len();
+
+    const NEG_INF: i32 = i32::MIN / 2;
+    const GAP_PENALTY: i32 = 1;
+    const BOUNDARY_BONUS: i32 = 5;
+    const CAMEL_HUMP_BONUS: i32 = 3;
+
+    // table[i][j] = best score aligning cand[..i] against query[..j].
+    // table[i][0] = 0: matching an empty query prefix is free.
+    // table[0][j>0] = NEG_INF: an empty candidate can't match any
+    // query characters.
+    let mut table = vec![vec![0; m + 1]; n + 1];
+    for row in table[0][1..=m].iter_mut() {
+        *row = NEG_INF;
+    }
+
+    for i in 1..=n {
+        for j in 1..=m {
+            let skip = table[i - 1][j] - GAP_PENALTY;
+
+            let matches = cand[i - 1].to_ascii_lowercase() == query[j - 1].to_ascii_lowercase();
+            let take = if matches {
+                let mut bonus = 1;
+                let at_boundary = i == 1
+                    || !cand[i - 2].is_alphanumeric()
+                    || cand[i - 2] == ':';
+                if at_boundary {
+                    bonus += BOUNDARY_BONUS;
+                }
+                let camel_hump = i > 1 && cand[i - 1].is_uppercase() && cand[i - 2].is_lowercase();
+                if camel_hump {
+                    bonus += CAMEL_HUMP_BONUS;
+                }
+                table[i - 1][j - 1] + bonus
+            } else {
+                NEG_INF
+            };
+
+            table[i][j] = skip.max(take);
+        }
+    }
+
+    let best = (0..=n).map(|i| table[i][m]).max().unwrap_or(NEG_INF);
+    if best <= NEG_INF / 2 {
+        None
+    } else {
+        Some(best)
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+use scraper::{Html, Selector};
+
+use crate::content::build_http_client;
+
+#[derive(Debug)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub errors: Vec<String>,
+}
+
+// Walks `root` (expected to be `target/doc/<crate>/`) and checks every
+// relative href in every `.html` file resolves to an existing file,
+// and that `#fragment` links resolve to an existing `id` in the
+// destination. External `http(s)://` links are skipped by default;
+// when `include_external` is set they're actually requested and
+// flagged if they fail or respond with a non-2xx status.
+pub fn check_tree(root: &Path, include_external: bool) -> Vec<FileError> {
+    let files = collect_html_files(root);
+
+    files
+        .par_iter()
+        .filter_map(|path| check_file(root, path, include_external))
+        .collect()
+}
+
+fn collect_html_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().and_then(|e| e.to_str()) == Some("html") {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn check_file(root: &Path, path: &Path, include_external: bool) -> Option<FileError> {
+    let content = fs::read_to_string(path).ok()?;
+    let html = Html::parse_document(&content);
+    let selector = Selector::parse("[href]").unwrap();
+
+    let mut errors = Vec::new();
+    for el in html.select(&selector) {
+        let href = match el.value().attr("href") {
+            Some(h) => h,
+            None => continue,
+        };
+
+        if href.starts_with("mailto:") {
+            continue;
+        }
+
+        if href.starts_with("http://") || href.starts_with("https://") {
+            if include_external {
+                if let Some(err) = check_external_link(href) {
+                    errors.push(err);
+                }
+            }
+            continue;
+        }
+
+        if href.is_empty() {
+            continue;
+        }
+
+        if let Some(frag) = href.strip_prefix('#') {
+            if !fragment_exists(&html, frag) {
+                errors.push(format!("broken fragment link: {}", href));
+            }
+            continue;
+        }
+
+        let (target, fragment) = match href.split_once('#') {
+            Some((t, f)) => (t, Some(f)),
+            None => (href, None),
+        };
+
+        let resolved = resolve_href(path, target);
+        if !resolved.is_file() {
+            errors.push(format!("broken link: {} -> {}", href, resolved.display()));
+            continue;
+        }
+
+        if let Some(frag) = fragment {
+            match fs::read_to_string(&resolved) {
+                Err(_) => errors.push(format!("could not read link target: {}", resolved.display())),
+                Ok(target_content) => {
+                    let target_html = Html::parse_document(&target_content);
+                    if !fragment_exists(&target_html, frag) {
+                        errors.push(format!("broken fragment link: {}", href));
+                    }
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(FileError {
+            path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            errors,
+        })
+    }
+}
+
+// Actually validates an external link with a HEAD request, rather
+// than just noting that it exists - a non-2xx response or an
+// unreachable host is reported as a broken link.
+fn check_external_link(url: &str) -> Option<String> {
+    let client = match build_http_client() {
+        Ok(c) => c,
+        Err(_) => return Some(format!("could not build http client to check: {}", url)),
+    };
+
+    match client.head(url).send() {
+        Ok(resp) if resp.status().is_success() => None,
+        Ok(resp) => Some(format!("external link returned {}: {}", resp.status(), url)),
+        Err(_) => Some(format!("unreachable external link: {}", url)),
+    }
+}
+
+// Joins `href` against the directory of `from_file`, the same way
+// `gen_doc_listings` joins relative hrefs against a page's base url,
+// then collapses `..`/`.` components.
+fn resolve_href(from_file: &Path, href: &str) -> PathBuf {
+    let base_dir = from_file.parent().unwrap_or_else(|| Path::new("."));
+    normalize_path(&base_dir.join(href))
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+fn fragment_exists(html: &Html, id: &str) -> bool {
+    html.select(&Selector::parse("[id]").unwrap())
+        .any(|el| el.value().attr("id") == Some(id))
+}
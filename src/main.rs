@@ -1,13 +1,30 @@
+mod cache;
 mod content;
+mod fuzzy;
+mod linkcheck;
 
 use rustyline::Editor;
+use std::env;
+use std::path::PathBuf;
 use std::process::Command;
 
+use prettytable::{format, Cell, Row, Table};
+
 use content::*;
 
+// How many ranked candidates to show when nothing matches exactly.
+const FUZZY_CANDIDATES: usize = 5;
+
+enum SearchResult {
+    Exact(DocListing),
+    Candidates(Vec<DocListing>),
+    None,
+}
+
 struct DocState {
     page: DocPage,
     available_docs: Vec<DocListing>,
+    last_doc_body: Option<String>,
 }
 
 impl From<DocPage> for DocState {
@@ -19,39 +36,83 @@ impl From<DocPage> for DocState {
 
         Self {
             page: page,
-            available_docs: listings
+            available_docs: listings,
+            last_doc_body: None,
         }
     }
 }
 
 impl DocState {
-    fn search_doc_listings(&self, target: &str) -> Option<DocListing> {
-        // First search for exact matches
+    fn search_doc_listings(&self, target: &str) -> SearchResult {
+        // First search for exact matches - these short-circuit the
+        // fuzzy ranking entirely.
         for doc in &self.available_docs {
             if &doc.name == target {
-                return Some(doc.clone())
+                return SearchResult::Exact(doc.clone())
             }
         }
 
-        // No exact matches - search for anything
-        // that ends with target. If you
-        // do this first you could fail to
-        // return an exact match if it comes later.
-        for doc in &self.available_docs {
-            if doc.name.ends_with(target) {
-                return Some(doc.clone())
-            }
+        // No exact match - rank everything by fuzzy subsequence score
+        // and surface the top candidates instead of giving up.
+        let mut scored: Vec<(i32, &DocListing)> = self.available_docs
+            .iter()
+            .filter_map(|d| fuzzy::score(&d.name, target).map(|s| (s, d)))
+            .collect();
+
+        if scored.is_empty() {
+            return SearchResult::None
         }
 
-        None
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+
+        SearchResult::Candidates(
+            scored.into_iter().take(FUZZY_CANDIDATES).map(|(_, d)| d.clone()).collect()
+        )
+    }
+
+    // Resolves `target` to an exact doc listing and fetches its body,
+    // storing it on `self` so the caller can display it without
+    // re-fetching. When `target` doesn't match exactly, returns the
+    // ranked candidates instead of guessing.
+    fn show_doc(&mut self, target: &str, online: bool) -> Result<DocLookup, ContentError> {
+        let listing = match self.search_doc_listings(target) {
+            SearchResult::Exact(d) => d,
+            SearchResult::Candidates(d) => return Ok(DocLookup::Candidates(d)),
+            SearchResult::None => return Err(ContentError::DoesNotExist),
+        };
+        // A missing page after a match is a load failure, not a miss.
+        let body = fetch_doc_body(&listing, online).map_err(|e| match e {
+            ContentError::DoesNotExist => ContentError::LoadFailure,
+            e => e,
+        })?;
+        self.last_doc_body = Some(body);
+        Ok(DocLookup::Body(self.last_doc_body.as_deref().unwrap()))
     }
 }
 
+enum DocLookup<'a> {
+    Body(&'a str),
+    Candidates(Vec<DocListing>),
+}
+
+fn print_candidates_table(candidates: &[DocListing]) {
+    let mut tbl = Table::new();
+    tbl.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    tbl.set_titles(Row::new(vec![
+        Cell::new("Did you mean?").style_spec("Fgb")
+    ]));
+    for c in candidates {
+        tbl.add_row(Row::new(vec![Cell::new(&c.name).style_spec("Fyi")]));
+    }
+    tbl.printstd();
+}
+
 enum Cmd {
     Doc(String),
     Lookup(String),
     Unknown(String),
     RefreshCache,
+    Check(bool),
     Empty,
     InvalidUsage(String),
 }
@@ -97,6 +158,13 @@ impl From<String> for Cmd {
                 }
             }
             "rc" => Self::RefreshCache,
+            "check" => {
+                match cmds.len() {
+                    1 => Self::Check(false),
+                    2 if cmds[1] == "ext" => Self::Check(true),
+                    _ => Self::InvalidUsage("check command takes no args, or `ext` to include external links".to_owned()),
+                }
+            }
             "doc" => {
                 if cmds.len() != 2 {
                     Self::InvalidUsage("doc command must be length 2".to_owned())
@@ -129,6 +197,21 @@ fn wait_for_permission(prompt: &str, editor: &mut Editor<()>) -> Allow {
     wait_for_input::<Allow>(&p, editor)
 }
 
+// Prints `body` a page at a time, pausing for input between pages so
+// long doc comments don't scroll straight off the terminal.
+fn print_paginated(body: &str, editor: &mut Editor<()>) {
+    const PAGE_SIZE: usize = 20;
+    let lines: Vec<&str> = body.lines().collect();
+    for (i, page) in lines.chunks(PAGE_SIZE).enumerate() {
+        if i > 0 {
+            let _ = editor.readline("-- more --");
+        }
+        for line in page {
+            println!("{}", line);
+        }
+    }
+}
+
 fn main() {
     let mut e = Editor::<()>::new();
     loop {
@@ -169,16 +252,16 @@ fn process_crate_fetch_cmds(online: bool, name: &str, editor: &mut Editor<()>) {
                 break;
             },
             Err(e) => println!("{:?}", e),
-            Ok(p) => process_opened_crate_cmds(p, &name, editor),
+            Ok(p) => process_opened_crate_cmds(p, online, &name, editor),
         }
     }
 }
 
-fn process_opened_crate_cmds(p: DocPage, name: &str, editor: &mut Editor<()>) {
+fn process_opened_crate_cmds(p: DocPage, online: bool, name: &str, editor: &mut Editor<()>) {
     // Here we have a valid doc page open.
     // Enter a new state where we are looping
     // cmds onto this doc page.
-    let state = DocState::from(p);
+    let mut state = DocState::from(p);
     state.page.print_tableview();
     loop {
         let cmd_prmpt = format!("( {} ) >>", &name);
@@ -186,11 +269,14 @@ fn process_opened_crate_cmds(p: DocPage, name: &str, editor: &mut Editor<()>) {
         match cmd {
             Cmd::Lookup(_) => process_cmd(cmd, editor),
             Cmd::Empty => continue,
-            Cmd::RefreshCache => process_refresh_cmd(editor),
+            Cmd::RefreshCache => process_refresh_online_cache_cmd(name, editor),
+            Cmd::Check(include_external) => process_check_cmd(name, include_external),
             Cmd::Doc(s) => {
-                match state.search_doc_listings(&s) {
-                    None => println!("Did not match any docs"),
-                    Some(d) => println!("{} {}", &d.name, &d.url),
+                match state.show_doc(&s, online) {
+                    Err(ContentError::DoesNotExist) => println!("Did not match any docs"),
+                    Err(e) => println!("Failed to load doc body: {:?}", e),
+                    Ok(DocLookup::Candidates(c)) => print_candidates_table(&c),
+                    Ok(DocLookup::Body(body)) => print_paginated(body, editor),
                 }
             }
             _ => continue,
@@ -209,3 +295,34 @@ fn process_refresh_cmd(editor: &mut Editor<()>) {
         }
     }
 }
+
+// Forces a fresh download of the open crate's cached docs.rs page,
+// rather than shelling out to `cargo doc` like `process_refresh_cmd`.
+fn process_refresh_online_cache_cmd(name: &str, editor: &mut Editor<()>) {
+    let confirm = wait_for_permission("Force re-download this crate's cached docs.rs page? >>", editor);
+    match Allow::from(confirm) {
+        Allow::Yes => {
+            match DocPage::refresh_cache(name) {
+                Ok(_) => println!("Cache refreshed for {}", name),
+                Err(e) => println!("Failed to refresh cache: {:?}", e),
+            }
+        },
+        Allow::No => println!("Skipping refresh."),
+    }
+}
+
+fn process_check_cmd(name: &str, include_external: bool) {
+    let root = PathBuf::from(format!("{}/target/doc/{}", env::current_dir().unwrap().display(), name));
+    let errors = linkcheck::check_tree(&root, include_external);
+    if errors.is_empty() {
+        println!("No broken links found.");
+        return;
+    }
+
+    for file_error in &errors {
+        println!("{}", file_error.path.display());
+        for e in &file_error.errors {
+            println!("  {}", e);
+        }
+    }
+}